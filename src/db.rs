@@ -1,28 +1,63 @@
-use std::fs::File;
-use std::sync::Arc;
+use std::fs;
+use std::path::Path;
 
-use serde_json::from_reader;
-use tokio::sync::Mutex;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
 
-use crate::models::Customer;
+/// Represents a pooled connection to the SQLite customers database
+pub type Db = Pool<SqliteConnectionManager>;
 
-/// Represents an in memory data store of customer data
-pub type Db = Arc<Mutex<Vec<Customer>>>;
+/// Path to the SQLite database file
+const DB_PATH: &str = "./data/customers.db";
 
+/// The `customers` table schema, applied by both `init_db` and the
+/// in-memory pool the handler tests reach for
+const CREATE_CUSTOMERS_TABLE: &str = "CREATE TABLE IF NOT EXISTS customers (
+    guid       TEXT PRIMARY KEY,
+    first_name TEXT NOT NULL,
+    last_name  TEXT NOT NULL,
+    email      TEXT NOT NULL,
+    address    TEXT NOT NULL
+)";
 
 /// Initializes the data store
-/// 
-/// Returns a Db type that either contains customer data
-/// or is empty.
+///
+/// Opens (creating if necessary) the SQLite database at `DB_PATH`,
+/// migrates the `customers` table into existence, and returns a
+/// connection pool that handlers check connections out of.
 pub fn init_db() -> Db {
-    let file = File::open("./data/customers.json");
-    match file {
-        Ok(json) => {
-            let customers = from_reader(json).unwrap();
-            Arc::new(Mutex::new(customers))
-        },
-        Err(_) => {
-            Arc::new(Mutex::new(Vec::new()))
-        }
+    if let Some(parent) = Path::new(DB_PATH).parent() {
+        fs::create_dir_all(parent).expect("failed to create database directory");
     }
-}
\ No newline at end of file
+
+    let manager = SqliteConnectionManager::file(DB_PATH);
+    let pool = Pool::new(manager).expect("failed to create SQLite connection pool");
+
+    pool.get()
+        .expect("failed to get connection from pool")
+        .execute(CREATE_CUSTOMERS_TABLE, [])
+        .expect("failed to migrate customers table");
+
+    pool
+}
+
+/// Builds a migrated, in-memory connection pool for handler tests
+///
+/// The pool is capped at a single connection so the `:memory:` database
+/// (and everything inserted into it) survives across the multiple
+/// `db.get()` calls a test makes.
+#[cfg(test)]
+pub(crate) fn test_db() -> Db {
+    let manager = SqliteConnectionManager::memory();
+    let pool = Pool::builder()
+        .max_size(1)
+        .build(manager)
+        .expect("failed to create in-memory SQLite pool");
+
+    pool.get()
+        .expect("failed to get connection from pool")
+        .execute(CREATE_CUSTOMERS_TABLE, [])
+        .expect("failed to migrate customers table");
+
+    pool
+}
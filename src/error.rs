@@ -0,0 +1,130 @@
+use serde::Serialize;
+use warp::{http::StatusCode, Rejection, Reply};
+
+/// Application-level errors that can arise while handling a request
+#[derive(Debug)]
+pub enum ApiError {
+    /// The requested customer does not exist
+    NotFound,
+
+    /// A customer with the given guid already exists
+    DuplicateGuid,
+
+    /// The request body failed validation
+    InvalidBody(String),
+
+    /// The database pool or a SQL statement failed
+    Database(String),
+}
+
+impl warp::reject::Reject for ApiError {}
+
+/// The JSON shape returned for every error response
+#[derive(Serialize)]
+struct ErrorResponse {
+    error_code: u16,
+    error_message: String,
+}
+
+/// Converts a rejection into a JSON error response
+///
+/// Handles both `ApiError` and warp's built-in rejections (oversized or
+/// malformed bodies, unmatched routes, disallowed methods) so the whole
+/// API speaks one consistent `{ error_code, error_message }` format.
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, std::convert::Infallible> {
+    let (code, message) = if err.is_not_found() {
+        (StatusCode::NOT_FOUND, "Resource not found".to_string())
+    } else if let Some(api_err) = err.find::<ApiError>() {
+        match api_err {
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "Customer not found".to_string()),
+            ApiError::DuplicateGuid => (
+                StatusCode::BAD_REQUEST,
+                "A customer with this guid already exists".to_string(),
+            ),
+            ApiError::InvalidBody(message) => (StatusCode::BAD_REQUEST, message.clone()),
+            ApiError::Database(message) => (StatusCode::INTERNAL_SERVER_ERROR, message.clone()),
+        }
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, e.to_string())
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large".to_string(),
+        )
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed".to_string(),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unhandled server error".to_string(),
+        )
+    };
+
+    let json = warp::reply::json(&ErrorResponse {
+        error_code: code.as_u16(),
+        error_message: message,
+    });
+
+    Ok(warp::reply::with_status(json, code))
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::hyper::body::to_bytes;
+
+    use super::*;
+
+    async fn status_and_body(rejection: Rejection) -> (StatusCode, serde_json::Value) {
+        let reply = handle_rejection(rejection).await.unwrap().into_response();
+        let status = reply.status();
+        let body = to_bytes(reply.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn not_found_maps_to_404() {
+        let (status, body) = status_and_body(warp::reject::custom(ApiError::NotFound)).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error_code"], 404);
+    }
+
+    #[tokio::test]
+    async fn duplicate_guid_maps_to_400() {
+        let (status, body) = status_and_body(warp::reject::custom(ApiError::DuplicateGuid)).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error_code"], 400);
+    }
+
+    #[tokio::test]
+    async fn invalid_body_maps_to_400_with_message() {
+        let (status, body) =
+            status_and_body(warp::reject::custom(ApiError::InvalidBody("bad email".to_string())))
+                .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error_message"], "bad email");
+    }
+
+    #[tokio::test]
+    async fn database_error_maps_to_500() {
+        let (status, body) =
+            status_and_body(warp::reject::custom(ApiError::Database("boom".to_string()))).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error_code"], 500);
+    }
+
+    #[tokio::test]
+    async fn unmatched_route_maps_to_404() {
+        let (status, body) = status_and_body(warp::reject::not_found()).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error_code"], 404);
+    }
+}
@@ -1,111 +1,557 @@
-use std::convert::Infallible;
-
-use warp::{self, http::StatusCode};
+use rusqlite::{params, OptionalExtension, ToSql};
+use uuid::Uuid;
+use warp::{self, http::StatusCode, reject};
 
 use crate::db::Db;
-use crate::models::Customer;
+use crate::error::ApiError;
+use crate::models::{Customer, ListOptions, NewCustomer};
+
+fn pool_error(err: r2d2::Error) -> warp::Rejection {
+    reject::custom(ApiError::Database(err.to_string()))
+}
+
+fn sql_error(err: rusqlite::Error) -> warp::Rejection {
+    reject::custom(ApiError::Database(err.to_string()))
+}
+
+/// Maps a failed `INSERT` into `customers` to `ApiError::DuplicateGuid`
+/// when it failed the `guid` PRIMARY KEY constraint, or `ApiError::Database`
+/// for anything else
+fn insert_error(err: rusqlite::Error) -> warp::Rejection {
+    match &err {
+        rusqlite::Error::SqliteFailure(sqlite_err, _)
+            if sqlite_err.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            reject::custom(ApiError::DuplicateGuid)
+        }
+        _ => sql_error(err),
+    }
+}
+
+/// Runs a blocking SQLite closure on the blocking thread pool so a slow
+/// or contended query doesn't stall the async worker thread it's called
+/// from
+async fn run_blocking<T, F>(f: F) -> Result<T, warp::Rejection>
+where
+    F: FnOnce() -> Result<T, warp::Rejection> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .expect("blocking database task panicked")
+}
+
+/// Validates that a new customer's required fields are non-empty and
+/// that `email` looks like a real address
+fn validate_new_customer(new_customer: &NewCustomer) -> Result<(), ApiError> {
+    if new_customer.first_name.trim().is_empty() {
+        return Err(ApiError::InvalidBody(
+            "first_name must not be empty".to_string(),
+        ));
+    }
+
+    if new_customer.last_name.trim().is_empty() {
+        return Err(ApiError::InvalidBody(
+            "last_name must not be empty".to_string(),
+        ));
+    }
+
+    if !is_valid_email(&new_customer.email) {
+        return Err(ApiError::InvalidBody(
+            "email must be a valid address".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// A basic `local@domain.tld` shape check; not a full RFC 5322 validator
+fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
 
 /// Returns a list of customers as JSON
-/// 
+///
+/// Results can be narrowed with `last_name`/`email` filters and paged
+/// with `offset`/`limit` query parameters.
+///
 /// # Arguments
-/// 
-/// * `db` - `Db` -> thread safe vector of Customer objects
-pub async fn list_customers(db: Db) -> Result<impl warp::Reply, Infallible> {
-    let customers = db.lock().await;
-    let customers: Vec<Customer> = customers.clone();
+///
+/// * `list_options` - `ListOptions` -> pagination and filter parameters
+/// * `db` - `Db` -> pooled connection to the customers database
+pub async fn list_customers(
+    list_options: ListOptions,
+    db: Db,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let customers = run_blocking(move || list_customers_sync(list_options, db)).await?;
+
     Ok(warp::reply::json(&customers))
 }
 
+fn list_customers_sync(
+    list_options: ListOptions,
+    db: Db,
+) -> Result<Vec<Customer>, warp::Rejection> {
+    let conn = db.get().map_err(pool_error)?;
+
+    let mut sql = String::from(
+        "SELECT guid, first_name, last_name, email, address FROM customers WHERE 1 = 1",
+    );
+    let mut query_params: Vec<&dyn ToSql> = Vec::new();
+
+    if let Some(last_name) = &list_options.last_name {
+        sql.push_str(" AND last_name = ?");
+        query_params.push(last_name);
+    }
+
+    if let Some(email) = &list_options.email {
+        sql.push_str(" AND email = ?");
+        query_params.push(email);
+    }
+
+    sql.push_str(" LIMIT ? OFFSET ?");
+    let limit: i64 = list_options.limit.map(|limit| limit as i64).unwrap_or(i64::MAX);
+    let offset: i64 = list_options.offset.map(|offset| offset as i64).unwrap_or(0);
+    query_params.push(&limit);
+    query_params.push(&offset);
+
+    let mut stmt = conn.prepare(&sql).map_err(sql_error)?;
+    stmt.query_map(query_params.as_slice(), |row| {
+        Ok(Customer {
+            guid: row.get(0)?,
+            first_name: row.get(1)?,
+            last_name: row.get(2)?,
+            email: row.get(3)?,
+            address: row.get(4)?,
+        })
+    })
+    .map_err(sql_error)?
+    .collect::<Result<Vec<Customer>, _>>()
+    .map_err(sql_error)
+}
+
 /// Creates a new customer
-/// 
-/// Adds a new customer object to the data store if the customer
-/// doesn't already exist
-/// 
+///
+/// Validates the request body, then inserts a new customer row. If the
+/// body omits `guid` (or supplies an empty one), the server generates a
+/// fresh v4 UUID. Returns the created customer as JSON with a
+/// `Location` header pointing at `/customers/{guid}`.
+///
 /// # Arguments
-/// 
-/// * `new_customer` - `Customer` type
-/// * `db` - `Db` -> thread safe vector of Customer objects
-pub async fn create_customer(new_customer: Customer, db: Db) -> Result<impl warp::Reply, Infallible> {
-    let mut customers = db.lock().await;
-
-    for customer in customers.iter() {
-        if customer.guid == new_customer.guid {
-            return Ok(StatusCode::BAD_REQUEST)
-        }
-    }
+///
+/// * `new_customer` - `NewCustomer` -> the request body
+/// * `db` - `Db` -> pooled connection to the customers database
+pub async fn create_customer(
+    new_customer: NewCustomer,
+    db: Db,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    validate_new_customer(&new_customer).map_err(reject::custom)?;
+
+    let guid = new_customer
+        .guid
+        .filter(|guid| !guid.is_empty())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let customer = Customer {
+        guid,
+        first_name: new_customer.first_name,
+        last_name: new_customer.last_name,
+        email: new_customer.email,
+        address: new_customer.address,
+    };
+
+    let inserted = customer.clone();
+    run_blocking(move || insert_customer_sync(inserted, db)).await?;
 
-    customers.push(new_customer);
+    let reply = warp::reply::with_status(warp::reply::json(&customer), StatusCode::CREATED);
+    let reply = warp::reply::with_header(reply, "Location", format!("/customers/{}", customer.guid));
 
-    Ok(StatusCode::CREATED)
+    Ok(reply)
+}
+
+/// Inserts a customer row, letting the `guid` PRIMARY KEY constraint be
+/// the single source of truth for uniqueness rather than a separate
+/// SELECT + INSERT, which would leave a window for two concurrent
+/// requests with the same guid to both pass a pre-check and then race
+/// on the insert.
+fn insert_customer_sync(customer: Customer, db: Db) -> Result<(), warp::Rejection> {
+    let conn = db.get().map_err(pool_error)?;
+
+    conn.execute(
+        "INSERT INTO customers (guid, first_name, last_name, email, address) VALUES (?, ?, ?, ?, ?)",
+        params![
+            customer.guid,
+            customer.first_name,
+            customer.last_name,
+            customer.email,
+            customer.address,
+        ],
+    )
+    .map_err(insert_error)?;
+
+    Ok(())
 }
 
 /// Gets a single customer from the data store
-/// 
+///
 /// Returns a JSON object of an existing customer. If the customer
-/// is not found, it returns a NOT FOUND status code.
+/// is not found, rejects with `ApiError::NotFound`.
+///
 /// # Arguments
-/// 
+///
 /// * `guid` - String -> the id of the customer to retrieve
-/// * `db` - `Db` -> the thread safe data store
-pub async fn get_customer(guid: String, db: Db) -> Result<Box<dyn warp::Reply>, Infallible> {
-    let customers = db.lock().await;
+/// * `db` - `Db` -> pooled connection to the customers database
+pub async fn get_customer(guid: String, db: Db) -> Result<impl warp::Reply, warp::Rejection> {
+    let customer = run_blocking(move || get_customer_sync(guid, db)).await?;
 
-    for customer in customers.iter() {
-        if customer.guid == guid {
-            return Ok(Box::new(warp::reply::json(customer)))
-        }
-    }
+    Ok(warp::reply::json(&customer))
+}
 
-    Ok(Box::new(StatusCode::NOT_FOUND))
+fn get_customer_sync(guid: String, db: Db) -> Result<Customer, warp::Rejection> {
+    let conn = db.get().map_err(pool_error)?;
+
+    conn.query_row(
+        "SELECT guid, first_name, last_name, email, address FROM customers WHERE guid = ?",
+        params![guid],
+        |row| {
+            Ok(Customer {
+                guid: row.get(0)?,
+                first_name: row.get(1)?,
+                last_name: row.get(2)?,
+                email: row.get(3)?,
+                address: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(sql_error)?
+    .ok_or_else(|| reject::custom(ApiError::NotFound))
 }
 
 /// Updates an existing customer
-/// 
+///
 /// Overwrites an existing customer in the data store and returns
-/// an OK status code. If the customer is not found, a NOT FOUND status
-/// code is returned.
-/// 
+/// an OK status code. If the customer is not found, rejects with
+/// `ApiError::NotFound`.
+///
+/// The `guid` in the URL path is authoritative for locating the row to
+/// update; the `guid` field in the request body is ignored.
+///
 /// # Arguments
-/// 
+///
+/// * `guid` - String -> the id (from the URL path) of the customer to update
 /// * `updated_customer` - `Customer` -> updated customer info
-/// * `db` - `Db` -> thread safe data store
-pub async fn update_customer(updated_customer: Customer, db: Db) -> Result<impl warp::Reply, Infallible> {
-    let mut customers = db.lock().await;
-
-    for customer in customers.iter_mut() {
-        if customer.guid == updated_customer.guid {
-            *customer = updated_customer;
-            return Ok(StatusCode::OK);
-        }
-    }
+/// * `db` - `Db` -> pooled connection to the customers database
+pub async fn update_customer(
+    guid: String,
+    updated_customer: Customer,
+    db: Db,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    run_blocking(move || update_customer_sync(guid, updated_customer, db)).await?;
 
-    Ok(StatusCode::NOT_FOUND)
+    Ok(StatusCode::OK)
 }
 
+fn update_customer_sync(
+    guid: String,
+    updated_customer: Customer,
+    db: Db,
+) -> Result<(), warp::Rejection> {
+    let conn = db.get().map_err(pool_error)?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE customers SET first_name = ?, last_name = ?, email = ?, address = ? WHERE guid = ?",
+            params![
+                updated_customer.first_name,
+                updated_customer.last_name,
+                updated_customer.email,
+                updated_customer.address,
+                guid,
+            ],
+        )
+        .map_err(sql_error)?;
+
+    if rows_affected > 0 {
+        Ok(())
+    } else {
+        Err(reject::custom(ApiError::NotFound))
+    }
+}
 
 /// Deletes a customer from the data store
-/// 
+///
 /// If the customer exists in the data store, the customer is
 /// removed and a NO CONTENT status code is returned. If the customer
-/// does not exist, a NOT FOUND status code is returned.
-/// 
+/// does not exist, rejects with `ApiError::NotFound`.
+///
 /// # Arguments
-/// 
+///
 /// * `guid` - String -> the id of the customer to delete
-/// * `db` - `Db` -> thread safe data store
-pub async fn delete_customer(guid: String, db: Db) -> Result<impl warp::Reply, Infallible> {
-    let mut customers = db.lock().await;
+/// * `db` - `Db` -> pooled connection to the customers database
+pub async fn delete_customer(guid: String, db: Db) -> Result<impl warp::Reply, warp::Rejection> {
+    run_blocking(move || delete_customer_sync(guid, db)).await?;
 
-    let customer_count = customers.len();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn delete_customer_sync(guid: String, db: Db) -> Result<(), warp::Rejection> {
+    let conn = db.get().map_err(pool_error)?;
 
-    customers.retain(|customer| {
-        customer.guid != guid
-    });
+    let rows_affected = conn
+        .execute("DELETE FROM customers WHERE guid = ?", params![guid])
+        .map_err(sql_error)?;
 
-    let deleted = customers.len() != customer_count;
-    if deleted {
-        Ok(StatusCode::NO_CONTENT)
+    if rows_affected > 0 {
+        Ok(())
     } else {
-        Ok(StatusCode::NOT_FOUND)
+        Err(reject::custom(ApiError::NotFound))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::hyper::body::to_bytes;
+    use warp::Reply;
+
+    use super::*;
+    use crate::db::test_db;
+
+    fn sample_new_customer() -> NewCustomer {
+        NewCustomer {
+            guid: None,
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            email: "ada@example.com".to_string(),
+            address: "1 Analytical Engine Way".to_string(),
+        }
+    }
+
+    async fn json_body<T: serde::de::DeserializeOwned>(reply: impl warp::Reply) -> T {
+        let body = to_bytes(reply.into_response().into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn api_error(rejection: &warp::Rejection) -> &ApiError {
+        rejection.find::<ApiError>().expect("expected an ApiError")
+    }
+
+    #[test]
+    fn is_valid_email_accepts_local_at_domain_dot_tld() {
+        assert!(is_valid_email("ada@example.com"));
+    }
+
+    #[test]
+    fn is_valid_email_rejects_missing_at() {
+        assert!(!is_valid_email("ada.example.com"));
+    }
+
+    #[test]
+    fn is_valid_email_rejects_domain_without_dot() {
+        assert!(!is_valid_email("ada@example"));
+    }
+
+    #[tokio::test]
+    async fn create_customer_generates_a_guid_when_omitted() {
+        let db = test_db();
+
+        let reply = create_customer(sample_new_customer(), db)
+            .await
+            .expect("create_customer should succeed");
+        let created: Customer = json_body(reply).await;
+
+        assert!(!created.guid.is_empty());
+        assert_eq!(created.first_name, "Ada");
+    }
+
+    #[tokio::test]
+    async fn create_customer_rejects_duplicate_guid() {
+        let db = test_db();
+
+        let mut new_customer = sample_new_customer();
+        new_customer.guid = Some("dup-guid".to_string());
+        create_customer(new_customer, db.clone())
+            .await
+            .expect("first create_customer should succeed");
+
+        let mut new_customer = sample_new_customer();
+        new_customer.guid = Some("dup-guid".to_string());
+        let rejection = create_customer(new_customer, db)
+            .await
+            .expect_err("second create_customer should be rejected");
+
+        assert!(matches!(api_error(&rejection), ApiError::DuplicateGuid));
+    }
+
+    #[tokio::test]
+    async fn create_customer_rejects_invalid_email() {
+        let db = test_db();
+
+        let mut new_customer = sample_new_customer();
+        new_customer.email = "not-an-email".to_string();
+        let rejection = create_customer(new_customer, db)
+            .await
+            .expect_err("create_customer should be rejected");
+
+        assert!(matches!(api_error(&rejection), ApiError::InvalidBody(_)));
+    }
+
+    #[tokio::test]
+    async fn get_customer_returns_a_created_customer() {
+        let db = test_db();
+
+        let reply = create_customer(sample_new_customer(), db.clone())
+            .await
+            .expect("create_customer should succeed");
+        let created: Customer = json_body(reply).await;
+
+        let reply = get_customer(created.guid.clone(), db)
+            .await
+            .expect("get_customer should succeed");
+        let fetched: Customer = json_body(reply).await;
+
+        assert_eq!(fetched.guid, created.guid);
+    }
+
+    #[tokio::test]
+    async fn get_customer_rejects_unknown_guid() {
+        let db = test_db();
+
+        let rejection = get_customer("missing".to_string(), db)
+            .await
+            .expect_err("get_customer should be rejected");
+
+        assert!(matches!(api_error(&rejection), ApiError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn update_customer_uses_the_path_guid_not_the_body_guid() {
+        let db = test_db();
+
+        let reply = create_customer(sample_new_customer(), db.clone())
+            .await
+            .expect("create_customer should succeed");
+        let created: Customer = json_body(reply).await;
+
+        let mut updated = created.clone();
+        updated.guid = "some-other-guid".to_string();
+        updated.first_name = "Grace".to_string();
+        update_customer(created.guid.clone(), updated, db.clone())
+            .await
+            .expect("update_customer should succeed");
+
+        let reply = get_customer(created.guid, db)
+            .await
+            .expect("get_customer should succeed");
+        let fetched: Customer = json_body(reply).await;
+
+        assert_eq!(fetched.first_name, "Grace");
+    }
+
+    #[tokio::test]
+    async fn update_customer_rejects_unknown_guid() {
+        let db = test_db();
+
+        let rejection = update_customer(
+            "missing".to_string(),
+            Customer {
+                guid: "missing".to_string(),
+                first_name: "Grace".to_string(),
+                last_name: "Hopper".to_string(),
+                email: "grace@example.com".to_string(),
+                address: "1 Compiler Way".to_string(),
+            },
+            db,
+        )
+        .await
+        .expect_err("update_customer should be rejected");
+
+        assert!(matches!(api_error(&rejection), ApiError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn delete_customer_removes_a_created_customer() {
+        let db = test_db();
+
+        let reply = create_customer(sample_new_customer(), db.clone())
+            .await
+            .expect("create_customer should succeed");
+        let created: Customer = json_body(reply).await;
+
+        delete_customer(created.guid.clone(), db.clone())
+            .await
+            .expect("delete_customer should succeed");
+
+        let rejection = get_customer(created.guid, db)
+            .await
+            .expect_err("get_customer should be rejected after delete");
+        assert!(matches!(api_error(&rejection), ApiError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn delete_customer_rejects_unknown_guid() {
+        let db = test_db();
+
+        let rejection = delete_customer("missing".to_string(), db)
+            .await
+            .expect_err("delete_customer should be rejected");
+
+        assert!(matches!(api_error(&rejection), ApiError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn list_customers_filters_and_paginates() {
+        let db = test_db();
+
+        for (first_name, last_name) in [
+            ("Ada", "Lovelace"),
+            ("Grace", "Hopper"),
+            ("Katherine", "Johnson"),
+        ] {
+            let mut new_customer = sample_new_customer();
+            new_customer.first_name = first_name.to_string();
+            new_customer.last_name = last_name.to_string();
+            new_customer.email = format!("{}@example.com", first_name.to_lowercase());
+            create_customer(new_customer, db.clone())
+                .await
+                .expect("create_customer should succeed");
+        }
+
+        let reply = list_customers(
+            ListOptions {
+                offset: None,
+                limit: None,
+                last_name: Some("Hopper".to_string()),
+                email: None,
+            },
+            db.clone(),
+        )
+        .await
+        .expect("list_customers should succeed");
+        let filtered: Vec<Customer> = json_body(reply).await;
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].last_name, "Hopper");
+
+        let reply = list_customers(
+            ListOptions {
+                offset: Some(1),
+                limit: Some(1),
+                last_name: None,
+                email: None,
+            },
+            db,
+        )
+        .await
+        .expect("list_customers should succeed");
+        let paged: Vec<Customer> = json_body(reply).await;
+        assert_eq!(paged.len(), 1);
+    }
+}
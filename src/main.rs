@@ -1,16 +1,45 @@
+use std::env;
+use std::net::SocketAddr;
+
 use warp;
 
 mod db;
+mod error;
 mod handlers;
 mod models;
 mod routes;
 
+/// Environment variable that overrides the access log target name
+const LOG_TARGET_ENV: &str = "LOG_TARGET";
+
+/// Default access log target; set `RUST_LOG=customers=info` to see it
+const DEFAULT_LOG_TARGET: &str = "customers";
+
+/// Environment variable that overrides the address the server binds to
+const BIND_ADDR_ENV: &str = "BIND_ADDR";
+
+/// Default address the server listens on when `BIND_ADDR` isn't set
+const DEFAULT_BIND_ADDR: &str = "127.0.0.1:3000";
+
 #[tokio::main]
 async fn main() {
+    pretty_env_logger::init();
+
     let db = db::init_db();
-    let customer_routes = routes::customer_routes(db);
 
-    warp::serve(customer_routes)
-        .run(([127, 0, 0, 1], 3000))
-        .await;
+    // `warp::log` requires a `&'static str`; leaking is fine since this
+    // only runs once at startup.
+    let log_target: &'static str = Box::leak(
+        env::var(LOG_TARGET_ENV)
+            .unwrap_or_else(|_| DEFAULT_LOG_TARGET.to_string())
+            .into_boxed_str(),
+    );
+    let customer_routes = routes::customer_routes(db, log_target);
+
+    let addr: SocketAddr = env::var(BIND_ADDR_ENV)
+        .unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string())
+        .parse()
+        .expect("BIND_ADDR must be a valid socket address");
+
+    warp::serve(customer_routes).run(addr).await;
 }
@@ -1,5 +1,24 @@
 use serde::{Deserialize, Serialize};
 
+/// Query parameters accepted by `GET /customers`
+///
+/// All fields are optional, so a bare `GET /customers` still returns
+/// the full, unfiltered list.
+#[derive(Debug, Deserialize)]
+pub struct ListOptions {
+    /// Number of customers to skip before collecting results
+    pub offset: Option<usize>,
+
+    /// Maximum number of customers to return
+    pub limit: Option<usize>,
+
+    /// Only include customers whose last name matches exactly
+    pub last_name: Option<String>,
+
+    /// Only include customers whose email matches exactly
+    pub email: Option<String>,
+}
+
 /// Represents a customer
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Customer {
@@ -18,3 +37,25 @@ pub struct Customer {
     /// Physical address
     pub address: String,
 }
+
+/// Body accepted by `POST /customers`
+///
+/// `guid` is optional; when omitted (or empty) the server generates a
+/// fresh v4 UUID for the new customer.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewCustomer {
+    /// A client-supplied identifier; generated by the server if absent
+    pub guid: Option<String>,
+
+    /// First name
+    pub first_name: String,
+
+    /// Last name
+    pub last_name: String,
+
+    /// Email address
+    pub email: String,
+
+    /// Physical address
+    pub address: String,
+}
@@ -2,18 +2,28 @@ use std::convert::Infallible;
 use warp::{self, Filter};
 
 use crate::db::Db;
+use crate::error::{handle_rejection, ApiError};
 use crate::handlers;
-use crate::models::Customer;
+use crate::models::{Customer, ListOptions, NewCustomer};
 
 /// All customer routes
+///
+/// # Arguments
+///
+/// * `db` - `Db` -> pooled connection to the customers database
+/// * `log_target` - name access logs are emitted under, e.g. via
+///   `RUST_LOG=<log_target>=info`
 pub fn customer_routes(
     db: Db,
-) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    log_target: &'static str,
+) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
     get_customer(db.clone())
         .or(update_customer(db.clone()))
         .or(delete_customer(db.clone()))
         .or(create_customer(db.clone()))
         .or(customers_list(db))
+        .recover(handle_rejection)
+        .with(warp::log(log_target))
 }
 
 /// GET /customers
@@ -22,6 +32,8 @@ fn customers_list(
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path("customers")
         .and(warp::get())
+        .and(warp::path::end())
+        .and(list_options())
         .and(with_db(db))
         .and_then(handlers::list_customers)
 }
@@ -32,7 +44,7 @@ fn create_customer(
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path("customers")
         .and(warp::post())
-        .and(json_body())
+        .and(new_customer_body())
         .and(with_db(db))
         .and_then(handlers::create_customer)
 }
@@ -75,3 +87,82 @@ fn with_db(db: Db) -> impl Filter<Extract = (Db,), Error = Infallible> + Clone {
 fn json_body() -> impl Filter<Extract = (Customer,), Error = warp::Rejection> + Clone {
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
+
+fn new_customer_body() -> impl Filter<Extract = (NewCustomer,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+/// Parses `?offset=`/`?limit=`/`?last_name=`/`?email=` into `ListOptions`,
+/// turning a malformed query string into an `ApiError::InvalidBody`
+/// rejection instead of warp's opaque, unhandled query rejection.
+fn list_options() -> impl Filter<Extract = (ListOptions,), Error = warp::Rejection> + Clone {
+    warp::query::<ListOptions>().or_else(|_| async move {
+        Err(warp::reject::custom(ApiError::InvalidBody(
+            "invalid query parameters: offset/limit must be non-negative integers".to_string(),
+        )))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    use super::*;
+    use crate::db::test_db;
+
+    fn test_routes() -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
+        customer_routes(test_db(), "customers-test")
+    }
+
+    #[tokio::test]
+    async fn malformed_query_params_reject_with_400() {
+        let resp = warp::test::request()
+            .path("/customers?limit=not-a-number")
+            .reply(&test_routes())
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn create_then_get_round_trips_through_the_full_filter_stack() {
+        let routes = test_routes();
+
+        let create_resp = warp::test::request()
+            .method("POST")
+            .path("/customers")
+            .json(&NewCustomer {
+                guid: None,
+                first_name: "Ada".to_string(),
+                last_name: "Lovelace".to_string(),
+                email: "ada@example.com".to_string(),
+                address: "1 Analytical Engine Way".to_string(),
+            })
+            .reply(&routes)
+            .await;
+        assert_eq!(create_resp.status(), StatusCode::CREATED);
+
+        let created: Customer = serde_json::from_slice(create_resp.body()).unwrap();
+
+        let get_resp = warp::test::request()
+            .path(&format!("/customers/{}", created.guid))
+            .reply(&routes)
+            .await;
+
+        assert_eq!(get_resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unknown_customer_returns_404_json() {
+        let resp = warp::test::request()
+            .path("/customers/missing")
+            .reply(&test_routes())
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let body: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["error_code"], 404);
+    }
+}